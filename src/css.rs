@@ -1,14 +1,14 @@
-struct StyleSheet {
-    rules: Vec<Rule>,
+pub(crate) struct StyleSheet {
+    pub(crate) rules: Vec<Rule>,
 }
 
 /// css rule, a rule is a style block, like `div.note { margin-bottom: 20px; padding: 10px; }`
-struct Rule {
+pub(crate) struct Rule {
     /// selector lists
-    selectors: Vec<Selector>,
+    pub(crate) selectors: Vec<Selector>,
 
     /// declaration lists
-    declarations: Vec<Declaration>,
+    pub(crate) declarations: Vec<Declaration>,
 }
 
 /// **Specificity** is one of the ways a rendering engine decides which style
@@ -44,59 +44,255 @@ struct Rule {
 ///
 pub type Specificity = (usize, usize, usize);
 
-/// selector of css, like a tag name、a class name prefixed by '.'、'*'
-enum Selector {
+/// selector of css, like a tag name、a class name prefixed by '.'、'*',
+/// optionally chained to an ancestor selector via a combinator,
+/// like `div p` or `ul > li`
+#[derive(Clone)]
+pub(crate) enum Selector {
     Simple(SimpleSelector),
+    Combinator {
+        ancestor: Box<Selector>,
+        combinator: Combinator,
+        subject: SimpleSelector,
+    },
+}
+
+/// how a selector's subject relates to its ancestor selector
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Combinator {
+    /// `ancestor subject`, matches any descendant
+    Descendant,
+
+    /// `ancestor > subject`, matches only the immediate parent
+    Child,
 }
 
 impl Selector {
-    /// get the specificity of a selector
-    fn specificity(&self) -> Specificity {
-        let Selector::Simple(ref simple) = *self;
-        let a = simple.id.iter().count();
-        let b = simple.class.len();
-        let c = simple.tag_name.iter().count();
-        (a, b, c)
+    /// get the specificity of a selector, summing across the whole chain
+    pub(crate) fn specificity(&self) -> Specificity {
+        match *self {
+            Selector::Simple(ref simple) => simple.specificity(),
+            Selector::Combinator {
+                ref ancestor,
+                ref subject,
+                ..
+            } => {
+                let (a1, b1, c1) = ancestor.specificity();
+                let (a2, b2, c2) = subject.specificity();
+                (a1 + a2, b1 + b2, c1 + c2)
+            }
+        }
     }
 }
 
 /// simple selector without any combinator
-struct SimpleSelector {
+#[derive(Clone)]
+pub(crate) struct SimpleSelector {
     /// tag name, like `div`
-    tag_name: Option<String>,
+    pub(crate) tag_name: Option<String>,
 
     /// id, like `#dog`
-    id: Option<String>,
+    pub(crate) id: Option<String>,
 
     /// class name, like `.apple`
-    class: Vec<String>,
+    pub(crate) class: Vec<String>,
+}
+
+impl SimpleSelector {
+    /// get the specificity of a single simple selector
+    fn specificity(&self) -> Specificity {
+        let a = self.id.iter().count();
+        let b = self.class.len();
+        let c = self.tag_name.iter().count();
+        (a, b, c)
+    }
 }
 
 /// a name/value pair, just a style unit. for example, "margin: auto;" is a declaration.
-struct Declaration {
-    name: String,
-    value: Value,
+pub(crate) struct Declaration {
+    pub(crate) name: String,
+    pub(crate) value: Value,
 }
 
-enum Value {
+#[derive(Clone)]
+pub(crate) enum Value {
     Keyword(String),
     Length(f32, Unit),
     ColorValue(Color),
 }
 
-enum Unit {
+#[derive(Clone)]
+pub(crate) enum Unit {
     Px,
 }
 
-struct Color {
-    r: u8,
-    g: u8,
-    b: u8,
-    a: u8,
+#[derive(Clone)]
+pub(crate) struct Color {
+    pub(crate) r: u8,
+    pub(crate) g: u8,
+    pub(crate) b: u8,
+    pub(crate) a: u8,
+}
+
+// -----------------------------
+// --- serialization to CSS ---
+// -----------------------------
+
+/// how a `StyleSheet` (and everything beneath it) should be serialized back to text
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum CssFormat {
+    /// no insignificant whitespace, everything on one line
+    Compact,
+
+    /// one rule/declaration per line, indented for readability
+    Pretty,
+}
+
+impl StyleSheet {
+    /// serialize this stylesheet back to CSS text
+    pub(crate) fn to_css(&self, format: CssFormat) -> String {
+        let mut out = String::new();
+        for rule in &self.rules {
+            rule.write_css(&mut out, format);
+        }
+        return out;
+    }
+}
+
+impl Rule {
+    /// write `<selectors> { <declarations> }` into `out`
+    fn write_css(&self, out: &mut String, format: CssFormat) {
+        for (i, selector) in self.selectors.iter().enumerate() {
+            if i > 0 {
+                out.push_str(if format == CssFormat::Pretty { ", " } else { "," });
+            }
+            selector.write_css(out, format);
+        }
+        out.push_str(if format == CssFormat::Pretty { " {\n" } else { "{" });
+        for declaration in &self.declarations {
+            if format == CssFormat::Pretty {
+                out.push_str("  ");
+            }
+            declaration.write_css(out, format);
+            if format == CssFormat::Pretty {
+                out.push('\n');
+            }
+        }
+        out.push('}');
+        if format == CssFormat::Pretty {
+            out.push('\n');
+        }
+    }
+}
+
+impl Selector {
+    /// write this selector, e.g. `div#id.class` or `ul > li` (collapsing the
+    /// child combinator to `>` with no surrounding spaces in compact mode)
+    fn write_css(&self, out: &mut String, format: CssFormat) {
+        match *self {
+            Selector::Simple(ref simple) => simple.write_css(out),
+            Selector::Combinator {
+                ref ancestor,
+                combinator,
+                ref subject,
+            } => {
+                ancestor.write_css(out, format);
+                out.push_str(match (combinator, format) {
+                    // descendant combinators always need the separating
+                    // space, or the two tag names would run together
+                    (Combinator::Descendant, _) => " ",
+                    (Combinator::Child, CssFormat::Pretty) => " > ",
+                    (Combinator::Child, CssFormat::Compact) => ">",
+                });
+                subject.write_css(out);
+            }
+        }
+    }
+}
+
+impl SimpleSelector {
+    /// write this simple selector, joining `tag#id.class` parts together
+    fn write_css(&self, out: &mut String) {
+        if let Some(ref tag_name) = self.tag_name {
+            out.push_str(tag_name);
+        }
+        if let Some(ref id) = self.id {
+            out.push('#');
+            out.push_str(id);
+        }
+        for class in &self.class {
+            out.push('.');
+            out.push_str(class);
+        }
+        if self.tag_name.is_none() && self.id.is_none() && self.class.is_empty() {
+            out.push('*');
+        }
+    }
+}
+
+impl Declaration {
+    /// write `<name>: <value>;` into `out`
+    fn write_css(&self, out: &mut String, format: CssFormat) {
+        out.push_str(&self.name);
+        out.push(':');
+        if format == CssFormat::Pretty {
+            out.push(' ');
+        }
+        self.value.write_css(out);
+        out.push(';');
+    }
+}
+
+impl Value {
+    /// write this value, e.g. `auto`, `20px`, `#ff0000`
+    fn write_css(&self, out: &mut String) {
+        match *self {
+            Value::Keyword(ref keyword) => out.push_str(keyword),
+            Value::Length(num, ref unit) => {
+                out.push_str(&format_number(num));
+                unit.write_css(out);
+            }
+            Value::ColorValue(ref color) => color.write_css(out),
+        }
+    }
+}
+
+impl Unit {
+    fn write_css(&self, out: &mut String) {
+        match *self {
+            Unit::Px => out.push_str("px"),
+        }
+    }
 }
 
-fn valid_identifier_char(identifier: char) -> bool {
-    todo!()
+impl Color {
+    /// write `#rrggbb`, or `rgba(r, g, b, a)` when the color isn't fully opaque
+    fn write_css(&self, out: &mut String) {
+        if self.a == 255 {
+            out.push_str(&format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b));
+        } else {
+            out.push_str(&format!(
+                "rgba({}, {}, {}, {})",
+                self.r,
+                self.g,
+                self.b,
+                self.a as f32 / 255.0
+            ));
+        }
+    }
+}
+
+/// format a number without a trailing `.0` for whole values
+fn format_number(num: f32) -> String {
+    if num.fract() == 0.0 {
+        format!("{}", num as i64)
+    } else {
+        format!("{}", num)
+    }
+}
+
+fn valid_identifier_char(identifier: u8) -> bool {
+    matches!(identifier, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_')
 }
 
 struct Parser {
@@ -113,7 +309,15 @@ impl Parser {
     // --------------------------
 
     fn parse_rules(&mut self) -> Vec<Rule> {
-        todo!()
+        let mut rules = Vec::new();
+        loop {
+            self.consume_whitespace();
+            if self.eof() {
+                break;
+            }
+            rules.push(self.parse_rule());
+        }
+        return rules;
     }
 
     /// parse a rule set: `<selector> { <declarations> }`
@@ -128,16 +332,16 @@ impl Parser {
     fn parse_selectors(&mut self) -> Vec<Selector> {
         let mut selectors = Vec::new();
         loop {
-            selectors.push(Selector::Simple(self.parse_simple_selector()));
+            selectors.push(self.parse_selector());
             self.consume_whitespace();
-            match self.next_char() {
-                ',' => {
-                    self.consume_char();
+            match self.peek_byte() {
+                b',' => {
+                    self.consume_byte();
                     self.consume_whitespace();
                 }
                 // start of declarations
-                '{' => break,
-                c => panic!("Unexpected character {} in selector list", c),
+                b'{' => break,
+                c => panic!("Unexpected character {} in selector list", c as char),
             }
         }
         // return selectors with highest specificity first, for use in matching
@@ -145,14 +349,55 @@ impl Parser {
         return selectors;
     }
 
+    /// parse a selector, following descendant (` `) and child (`>`) combinators
+    /// into the next simple selector, e.g.: `div p` or `ul > li.red`
+    fn parse_selector(&mut self) -> Selector {
+        let mut selector = Selector::Simple(self.parse_simple_selector());
+
+        loop {
+            let before_whitespace = self.pos;
+            self.consume_whitespace();
+            if self.eof() {
+                break;
+            }
+            match self.peek_byte() {
+                b',' | b'{' => {
+                    // trailing whitespace before the next selector/declarations
+                    self.pos = before_whitespace;
+                    break;
+                }
+                b'>' => {
+                    self.consume_byte();
+                    self.consume_whitespace();
+                    selector = Selector::Combinator {
+                        ancestor: Box::new(selector),
+                        combinator: Combinator::Child,
+                        subject: self.parse_simple_selector(),
+                    };
+                }
+                _ if self.pos > before_whitespace => {
+                    // whitespace followed by another simple selector
+                    selector = Selector::Combinator {
+                        ancestor: Box::new(selector),
+                        combinator: Combinator::Descendant,
+                        subject: self.parse_simple_selector(),
+                    };
+                }
+                c => panic!("Unexpected character {} in selector", c as char),
+            }
+        }
+
+        return selector;
+    }
+
     /// parse a list of declarations enclosed in `{ ... }`
     fn parse_declarations(&mut self) -> Vec<Declaration> {
-        assert_eq!(self.consume_char(), '{');
+        assert_eq!(self.consume_byte(), b'{');
         let mut declarations = Vec::new();
         loop {
             self.consume_whitespace();
-            if self.next_char() == '}' {
-                self.consume_char();
+            if self.peek_byte() == b'}' {
+                self.consume_byte();
                 break;
             }
             declarations.push(self.parse_declaration());
@@ -169,20 +414,20 @@ impl Parser {
         };
 
         while !self.eof() {
-            match self.next_char() {
-                '#' => {
-                    self.consume_char();
+            match self.peek_byte() {
+                b'#' => {
+                    self.consume_byte();
                     selector.id = Some(self.parse_identifier());
                 }
-                '.' => {
-                    self.consume_char();
+                b'.' => {
+                    self.consume_byte();
                     selector.class.push(self.parse_identifier());
                 }
-                '*' => {
-                    self.consume_char();
+                b'*' => {
+                    self.consume_byte();
                 }
-                // if `c` is true for method `valid_identifier_char`, use this arm
-                c if valid_identifier_char(c) => {
+                // if `b` is true for method `valid_identifier_char`, use this arm
+                b if valid_identifier_char(b) => {
                     selector.tag_name = Some(self.parse_identifier());
                 }
                 _ => break,
@@ -196,11 +441,11 @@ impl Parser {
     fn parse_declaration(&mut self) -> Declaration {
         let property_name = self.parse_identifier();
         self.consume_whitespace();
-        assert_eq!(self.consume_char(), ':');
+        assert_eq!(self.consume_byte(), b':');
         self.consume_whitespace();
         let value = self.parse_value();
         self.consume_whitespace();
-        assert_eq!(self.consume_char(), ';');
+        assert_eq!(self.consume_byte(), b';');
 
         Declaration {
             name: property_name,
@@ -209,20 +454,73 @@ impl Parser {
     }
 
     fn parse_identifier(&mut self) -> String {
-        todo!()
+        self.consume_until(valid_identifier_char).to_string()
     }
 
+    /// parse a property value: a length like `20px`, a color like `#ff0000`,
+    /// or a keyword like `auto`
     fn parse_value(&mut self) -> Value {
-        todo!()
+        match self.peek_byte() {
+            b'0'..=b'9' | b'.' => self.parse_length(),
+            b'#' => self.parse_color(),
+            _ => Value::Keyword(self.parse_identifier()),
+        }
+    }
+
+    /// parse a length, e.g. `20px`
+    fn parse_length(&mut self) -> Value {
+        let num = self.parse_float();
+        let unit = self.parse_unit();
+        Value::Length(num, unit)
+    }
+
+    /// parse a floating point number
+    fn parse_float(&mut self) -> f32 {
+        self.consume_until(|b| matches!(b, b'0'..=b'9' | b'.'))
+            .parse()
+            .unwrap()
+    }
+
+    /// parse a unit keyword, e.g. `px`
+    fn parse_unit(&mut self) -> Unit {
+        match &*self.parse_identifier().to_ascii_lowercase() {
+            "px" => Unit::Px,
+            u => panic!("Unrecognized unit {}", u),
+        }
+    }
+
+    /// parse a color value, e.g. `#cc0000`
+    fn parse_color(&mut self) -> Value {
+        assert_eq!(self.consume_byte(), b'#');
+        Value::ColorValue(Color {
+            r: self.parse_hex_pair(),
+            g: self.parse_hex_pair(),
+            b: self.parse_hex_pair(),
+            a: 255,
+        })
+    }
+
+    /// parse two hex digits into a `u8`
+    fn parse_hex_pair(&mut self) -> u8 {
+        let s = &self.input[self.pos..self.pos + 2];
+        self.pos += 2;
+        u8::from_str_radix(s, 16).unwrap()
     }
 
     // ----------------------
     // --- utils function ---
     // ----------------------
 
-    /// return next character
-    fn next_char(&self) -> char {
-        self.input[self.pos..].chars().next().unwrap()
+    /// return the next byte without consuming it
+    fn peek_byte(&self) -> u8 {
+        self.input.as_bytes()[self.pos]
+    }
+
+    /// return the current byte and advance self.pos past it
+    fn consume_byte(&mut self) -> u8 {
+        let cur_byte = self.peek_byte();
+        self.pos += 1;
+        cur_byte
     }
 
     /// return `true` if all input is consumed
@@ -230,30 +528,151 @@ impl Parser {
         self.pos >= self.input.len()
     }
 
-    /// return the current character and advance self.pos to the next character
-    fn consume_char(&mut self) -> char {
-        let mut iter = self.input[self.pos..].char_indices();
-        let (_, cur_char) = iter.next().unwrap();
-        // if all input is consumed, add `1` to indicate ending of input
-        let (next_pos, _) = iter.next().unwrap_or((1, ' '));
-        self.pos += next_pos;
-        return cur_char;
-    }
-
-    /// consume characters until `filter` function return false
-    fn consume_until<F>(&mut self, filter: F) -> String
+    /// consume bytes until `filter` returns `false`, returning the consumed
+    /// range as a borrowed slice instead of allocating a new `String`
+    fn consume_until<F>(&mut self, filter: F) -> &str
     where
-        F: Fn(char) -> bool,
+        F: Fn(u8) -> bool,
     {
-        let mut result = String::new();
-        while !self.eof() && filter(self.next_char()) {
-            result.push(self.consume_char());
+        let start = self.pos;
+        while !self.eof() && filter(self.peek_byte()) {
+            self.pos += 1;
         }
-        return result;
+        &self.input[start..self.pos]
     }
 
     /// consume and discard whitespace characters
     fn consume_whitespace(&mut self) {
-        self.consume_until(char::is_whitespace);
+        self.consume_until(|b| b.is_ascii_whitespace());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser(input: &str) -> Parser {
+        Parser {
+            pos: 0,
+            input: input.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_hex_color() {
+        let mut p = parser("#ff0000");
+        match p.parse_value() {
+            Value::ColorValue(Color { r, g, b, a }) => assert_eq!((r, g, b, a), (255, 0, 0, 255)),
+            _ => panic!("expected a color value"),
+        }
+    }
+
+    #[test]
+    fn parses_length() {
+        let mut p = parser("20px");
+        match p.parse_value() {
+            Value::Length(n, Unit::Px) => assert_eq!(n, 20.0),
+            _ => panic!("expected a length value"),
+        }
+    }
+
+    #[test]
+    fn parses_keyword() {
+        let mut p = parser("auto");
+        match p.parse_value() {
+            Value::Keyword(k) => assert_eq!(k, "auto"),
+            _ => panic!("expected a keyword value"),
+        }
+    }
+
+    #[test]
+    fn parses_rules_until_eof() {
+        let mut p = parser("div { margin: 20px; } .note { color: #112233; }");
+        let rules = p.parse_rules();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].declarations.len(), 1);
+        assert_eq!(rules[1].declarations.len(), 1);
+    }
+
+    #[test]
+    fn parses_descendant_and_child_combinators() {
+        let mut p = parser("div p { color: red; } ul > li { color: blue; }");
+        let rules = p.parse_rules();
+        assert_eq!(rules.len(), 2);
+        match &rules[0].selectors[0] {
+            Selector::Combinator { combinator, .. } => {
+                assert!(matches!(combinator, Combinator::Descendant))
+            }
+            _ => panic!("expected a descendant combinator selector"),
+        }
+        match &rules[1].selectors[0] {
+            Selector::Combinator { combinator, .. } => {
+                assert!(matches!(combinator, Combinator::Child))
+            }
+            _ => panic!("expected a child combinator selector"),
+        }
+    }
+
+    #[test]
+    fn combinator_specificity_sums_across_the_chain() {
+        let mut p = parser("ul.list > li#item");
+        let selector = p.parse_selector();
+        assert_eq!(selector.specificity(), (1, 1, 2));
+    }
+
+    #[test]
+    fn compact_mode_collapses_child_combinator_spacing() {
+        let mut p = parser("div > p.a, ul li { color: red; }");
+        let stylesheet = StyleSheet {
+            rules: p.parse_rules(),
+        };
+        assert_eq!(
+            stylesheet.to_css(CssFormat::Compact),
+            "div>p.a,ul li{color:red;}"
+        );
+    }
+
+    #[test]
+    fn pretty_mode_renders_one_rule_per_line_with_indented_declarations() {
+        let mut p = parser("div > p.a { margin: 20px; }");
+        let stylesheet = StyleSheet {
+            rules: p.parse_rules(),
+        };
+        assert_eq!(
+            stylesheet.to_css(CssFormat::Pretty),
+            "div > p.a {\n  margin: 20px;\n}\n"
+        );
+    }
+
+    #[test]
+    fn opaque_color_writes_as_hex() {
+        let color = Color {
+            r: 0x11,
+            g: 0x22,
+            b: 0x33,
+            a: 255,
+        };
+        let mut out = String::new();
+        color.write_css(&mut out);
+        assert_eq!(out, "#112233");
+    }
+
+    #[test]
+    fn translucent_color_writes_as_rgba() {
+        let color = Color {
+            r: 10,
+            g: 20,
+            b: 30,
+            a: 128,
+        };
+        let mut out = String::new();
+        color.write_css(&mut out);
+        assert_eq!(out, "rgba(10, 20, 30, 0.5019608)");
+    }
+
+    #[test]
+    fn format_number_drops_trailing_zero_for_whole_values() {
+        assert_eq!(format_number(20.0), "20");
+        assert_eq!(format_number(1.5), "1.5");
     }
 }
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 pub type AttrMap = HashMap<String, String>;
 
@@ -10,7 +11,7 @@ pub struct Node {
     node_type: NodeType,
 }
 
-enum NodeType {
+pub(crate) enum NodeType {
     /// text node
     Text(String),
 
@@ -18,7 +19,7 @@ enum NodeType {
     Element(ElementData),
 }
 
-struct ElementData {
+pub(crate) struct ElementData {
     /// tag name of element node,
     /// like `div`、`p`
     tag_name: String,
@@ -47,4 +48,34 @@ impl Node {
             }),
         }
     }
+
+    /// children of this node
+    pub(crate) fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    /// the type of this node
+    pub(crate) fn node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+}
+
+impl ElementData {
+    /// tag name of this element, like `div`
+    pub(crate) fn tag_name(&self) -> &str {
+        &self.tag_name
+    }
+
+    /// the `id` attribute of this element, if any
+    pub(crate) fn id(&self) -> Option<&str> {
+        self.attributes.get("id").map(|s| s.as_str())
+    }
+
+    /// the space-separated `class` attribute of this element, as a set
+    pub(crate) fn classes(&self) -> HashSet<&str> {
+        match self.attributes.get("class") {
+            Some(classes) => classes.split_whitespace().collect(),
+            None => HashSet::new(),
+        }
+    }
 }
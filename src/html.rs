@@ -1,5 +1,17 @@
 use crate::dom;
 
+/// look up one of the handful of named HTML character references we support
+fn named_char_reference(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => None,
+    }
+}
+
 struct Parser {
     /// the index of the next character we haven't processed yet
     pos: usize,
@@ -9,9 +21,16 @@ struct Parser {
 }
 
 impl Parser {
-    /// return next character
-    fn next_char(&self) -> char {
-        self.input[self.pos..].chars().next().unwrap()
+    /// return the next byte without consuming it
+    fn peek_byte(&self) -> u8 {
+        self.input.as_bytes()[self.pos]
+    }
+
+    /// return the current byte and advance self.pos past it
+    fn consume_byte(&mut self) -> u8 {
+        let cur_byte = self.peek_byte();
+        self.pos += 1;
+        cur_byte
     }
 
     /// return `true` if next characters start with the given string `s`
@@ -24,7 +43,9 @@ impl Parser {
         self.pos >= self.input.len()
     }
 
-    /// return the current character and advance self.pos to the next character
+    /// return the current character and advance self.pos to the next character;
+    /// unlike `consume_byte`, this decodes a full (possibly multi-byte) UTF-8
+    /// character, which `parse_text`'s decode loop needs for non-ASCII content
     fn consume_char(&mut self) -> char {
         let mut iter = self.input[self.pos..].char_indices();
         let (_, cur_char) = iter.next().unwrap();
@@ -34,72 +55,234 @@ impl Parser {
         return cur_char;
     }
 
-    /// consume characters until `filter` function return false
-    fn consume_until<F>(&mut self, filter: F) -> String
+    /// consume bytes until `filter` returns `false`, returning the consumed
+    /// range as a borrowed slice instead of allocating a new `String`
+    fn consume_until<F>(&mut self, filter: F) -> &str
     where
-        F: Fn(char) -> bool,
+        F: Fn(u8) -> bool,
     {
-        let mut result = String::new();
-        while !self.eof() && filter(self.next_char()) {
-            result.push(self.consume_char());
+        let start = self.pos;
+        while !self.eof() && filter(self.peek_byte()) {
+            self.pos += 1;
         }
-        return result;
+        &self.input[start..self.pos]
     }
 
     /// consume and discard whitespace characters
     fn consume_whitespace(&mut self) {
         // wow, so interesting! Please notice the `;`, so the result of consume_until won't be returned
-        self.consume_until(char::is_whitespace);
+        self.consume_until(|b| b.is_ascii_whitespace());
     }
 
     /// parse a tag or attribute name
     fn parse_tag_name(&mut self) -> String {
-        self.consume_until(|c| match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' => true,
-            _ => false,
-        })
+        self.consume_until(|b| matches!(b, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9'))
+            .to_string()
     }
 
     /// parse a single node
     fn parse_node(&mut self) -> dom::Node {
-        match self.next_char() {
-            '<' => self.parse_element(),
+        match self.peek_byte() {
+            b'<' => self.parse_element(),
             _ => self.parse_text(),
         }
     }
 
-    /// parse a text node
+    /// parse a text node, decoding character references (`&amp;`, `&#65;`, ...)
     fn parse_text(&mut self) -> dom::Node {
-        dom::Node::text(self.consume_until(|c| c != '<'))
+        let mut result = String::new();
+        while !self.eof() && self.peek_byte() != b'<' {
+            if self.peek_byte() == b'&' {
+                result.push_str(&self.parse_char_reference());
+            } else {
+                result.push(self.consume_char());
+            }
+        }
+        dom::Node::text(result)
+    }
+
+    /// parse a single character reference starting at `&`, returning the
+    /// decoded text (the literal `&` if the reference isn't recognized)
+    fn parse_char_reference(&mut self) -> String {
+        let start = self.pos;
+        assert_eq!(self.consume_byte(), b'&');
+
+        let code_point = if !self.eof() && self.peek_byte() == b'#' {
+            self.consume_byte();
+            let hex = !self.eof() && matches!(self.peek_byte(), b'x' | b'X');
+            if hex {
+                self.consume_byte();
+            }
+            let digits = if hex {
+                self.consume_until(|b| b.is_ascii_hexdigit()).to_string()
+            } else {
+                self.consume_until(|b| b.is_ascii_digit()).to_string()
+            };
+            if digits.is_empty() {
+                None
+            } else if hex {
+                u32::from_str_radix(&digits, 16).ok()
+            } else {
+                digits.parse().ok()
+            }
+            .and_then(char::from_u32)
+        } else {
+            let name = self.consume_until(|b| b.is_ascii_alphabetic()).to_string();
+            named_char_reference(&name)
+        };
+
+        match code_point {
+            Some(c) if !self.eof() && self.peek_byte() == b';' => {
+                self.consume_byte();
+                c.to_string()
+            }
+            _ => {
+                // not a valid terminated entity; emit the literal `&` and
+                // resume scanning right after it
+                self.pos = start + 1;
+                "&".to_string()
+            }
+        }
     }
 
     /// parse a single element, including its opening tag, contents, and closing tag
     fn parse_element(&mut self) -> dom::Node {
-      // opening tag
-      assert!(self.consume_char() == '<');
-      let tag_name = self.parse_tag_name();
-      let attrs = self.parse_attributes();
-      assert!(self.consume_char() == '>');
+        // opening tag
+        assert_eq!(self.consume_byte(), b'<');
+        let tag_name = self.parse_tag_name();
+        let attrs = self.parse_attributes();
+        // tolerate a self-closing-style trailing slash, e.g. `<br/>`
+        let self_closing = self.peek_byte() == b'/';
+        if self_closing {
+            self.consume_byte();
+        }
+        assert_eq!(self.consume_byte(), b'>');
+        if self_closing {
+            return dom::Node::element(tag_name, attrs, Vec::new());
+        }
 
-      // contents
-      let children = self.parse_nodes();
+        // contents
+        let children = self.parse_nodes();
 
-      // closing tag
-      assert!(self.consume_char() == '<');
-      assert!(self.consume_char() == '/');
-      assert!(self.parse_tag_name() == tag_name);
-      assert!(self.consume_char() == '>');
+        // closing tag
+        assert_eq!(self.consume_byte(), b'<');
+        assert_eq!(self.consume_byte(), b'/');
+        assert!(self.parse_tag_name() == tag_name);
+        assert_eq!(self.consume_byte(), b'>');
 
-      return dom::Node::element(tag_name, attrs, children);
+        return dom::Node::element(tag_name, attrs, children);
     }
 
+    /// parse a list of `name="value"` pairs, stopping at `>` or a trailing `/`
     fn parse_attributes(&mut self) -> dom::AttrMap {
-      // TODO
-      dom::AttrMap::new()
+        let mut attributes = dom::AttrMap::new();
+        loop {
+            self.consume_whitespace();
+            if self.peek_byte() == b'>' || self.peek_byte() == b'/' {
+                break;
+            }
+            let (name, value) = self.parse_attr();
+            attributes.insert(name, value);
+        }
+        return attributes;
     }
 
+    /// parse a single `name="value"` pair
+    fn parse_attr(&mut self) -> (String, String) {
+        let name = self.parse_tag_name();
+        self.consume_whitespace();
+        assert_eq!(self.consume_byte(), b'=');
+        self.consume_whitespace();
+        let value = self.parse_attr_value();
+        return (name, value);
+    }
+
+    /// parse a quoted attribute value, supporting both `"` and `'` delimiters
+    fn parse_attr_value(&mut self) -> String {
+        let open_quote = self.consume_byte();
+        assert!(open_quote == b'"' || open_quote == b'\'');
+        let value = self.consume_until(|b| b != open_quote).to_string();
+        assert_eq!(self.consume_byte(), open_quote);
+        return value;
+    }
+
+    /// parse a sequence of sibling nodes, stopping at EOF or a closing tag
     fn parse_nodes(&mut self) -> Vec<dom::Node> {
-      // TODO
-      Vec::new()
+        let mut nodes = Vec::new();
+        loop {
+            self.consume_whitespace();
+            if self.eof() || self.starts_with("</") {
+                break;
+            }
+            nodes.push(self.parse_node());
+        }
+        return nodes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser(input: &str) -> Parser {
+        Parser {
+            pos: 0,
+            input: input.to_string(),
+        }
+    }
+
+    fn text_of(node: &dom::Node) -> &str {
+        match node.node_type() {
+            dom::NodeType::Text(s) => s,
+            _ => panic!("expected a text node"),
+        }
+    }
+
+    #[test]
+    fn decodes_named_entity() {
+        let mut p = parser("Tom &amp; Jerry<");
+        assert_eq!(text_of(&p.parse_text()), "Tom & Jerry");
+    }
+
+    #[test]
+    fn decodes_decimal_numeric_entity() {
+        let mut p = parser("&#65;<");
+        assert_eq!(text_of(&p.parse_text()), "A");
+    }
+
+    #[test]
+    fn decodes_hex_numeric_entity() {
+        let mut p = parser("&#x41;<");
+        assert_eq!(text_of(&p.parse_text()), "A");
+    }
+
+    #[test]
+    fn emits_literal_ampersand_when_unterminated() {
+        let mut p = parser("Q&A<");
+        assert_eq!(text_of(&p.parse_text()), "Q&A");
+    }
+
+    #[test]
+    fn parses_double_and_single_quoted_attrs() {
+        let mut p = parser(r#"class="foo" checked='bar'>"#);
+        let attrs = p.parse_attributes();
+        assert_eq!(attrs.get("class").map(String::as_str), Some("foo"));
+        assert_eq!(attrs.get("checked").map(String::as_str), Some("bar"));
+    }
+
+    #[test]
+    fn duplicate_attribute_last_wins() {
+        let mut p = parser(r#"id="first" id="second">"#);
+        let attrs = p.parse_attributes();
+        assert_eq!(attrs.get("id").map(String::as_str), Some("second"));
+    }
+
+    #[test]
+    fn tolerates_self_closing_slash() {
+        let mut p = parser(r#"id="x"/>"#);
+        let attrs = p.parse_attributes();
+        assert_eq!(attrs.get("id").map(String::as_str), Some("x"));
+        // the trailing slash is left for `parse_element` to consume
+        assert_eq!(p.peek_byte(), b'/');
     }
 }
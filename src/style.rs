@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use crate::css::{Combinator, Rule, Selector, SimpleSelector, Specificity, StyleSheet, Value};
+use crate::dom::{ElementData, Node, NodeType};
+
+/// map from CSS property name to its value, for a single node
+pub(crate) type PropertyMap = HashMap<String, Value>;
+
+/// a node with all matching style rules applied to it
+pub(crate) struct StyledNode<'a> {
+    pub(crate) node: &'a Node,
+    pub(crate) specified_values: PropertyMap,
+    pub(crate) children: Vec<StyledNode<'a>>,
+}
+
+/// apply a stylesheet to an entire DOM tree, returning a StyledNode tree
+pub(crate) fn style_tree<'a>(root: &'a Node, stylesheet: &'a StyleSheet) -> StyledNode<'a> {
+    style_tree_with_ancestors(root, stylesheet, &[])
+}
+
+/// build a `StyledNode`, given the chain of element ancestors from the root
+/// down to (but not including) `node`, needed to match combinator selectors
+fn style_tree_with_ancestors<'a>(
+    node: &'a Node,
+    stylesheet: &'a StyleSheet,
+    ancestors: &[&'a ElementData],
+) -> StyledNode<'a> {
+    let mut child_ancestors = ancestors.to_vec();
+    let specified_values = match node.node_type() {
+        NodeType::Element(elem) => {
+            let values = specified_values(elem, ancestors, stylesheet);
+            child_ancestors.push(elem);
+            values
+        }
+        NodeType::Text(_) => HashMap::new(),
+    };
+
+    StyledNode {
+        node,
+        specified_values,
+        children: node
+            .children()
+            .iter()
+            .map(|child| style_tree_with_ancestors(child, stylesheet, &child_ancestors))
+            .collect(),
+    }
+}
+
+/// apply styles to a single element, returning the specified values
+fn specified_values(
+    elem: &ElementData,
+    ancestors: &[&ElementData],
+    stylesheet: &StyleSheet,
+) -> PropertyMap {
+    let mut values = HashMap::new();
+    let mut rules = matching_rules(elem, ancestors, stylesheet);
+
+    // go through the rules from lowest to highest specificity
+    rules.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+    for (_, rule) in rules {
+        for declaration in &rule.declarations {
+            values.insert(declaration.name.clone(), declaration.value.clone());
+        }
+    }
+    return values;
+}
+
+/// a single CSS rule, together with its specificity within that rule's selector
+type MatchedRule<'a> = (Specificity, &'a Rule);
+
+/// find all CSS rules that match the given element
+fn matching_rules<'a>(
+    elem: &ElementData,
+    ancestors: &[&ElementData],
+    stylesheet: &'a StyleSheet,
+) -> Vec<MatchedRule<'a>> {
+    stylesheet
+        .rules
+        .iter()
+        .filter_map(|rule| match_rule(elem, ancestors, rule))
+        .collect()
+}
+
+/// if `rule` matches `elem`, return a `MatchedRule`; otherwise return `None`
+fn match_rule<'a>(
+    elem: &ElementData,
+    ancestors: &[&ElementData],
+    rule: &'a Rule,
+) -> Option<MatchedRule<'a>> {
+    rule.selectors
+        .iter()
+        .find(|selector| matches(elem, ancestors, selector))
+        .map(|selector| (selector.specificity(), rule))
+}
+
+/// test whether a selector matches `elem`, given its chain of ancestors
+/// (ordered from the root down to the immediate parent)
+fn matches(elem: &ElementData, ancestors: &[&ElementData], selector: &Selector) -> bool {
+    match *selector {
+        Selector::Simple(ref simple) => matches_simple_selector(elem, simple),
+        Selector::Combinator {
+            ref ancestor,
+            combinator,
+            ref subject,
+        } => {
+            if !matches_simple_selector(elem, subject) {
+                return false;
+            }
+            match combinator {
+                Combinator::Child => match ancestors.split_last() {
+                    Some((parent, grandparents)) => matches(parent, grandparents, ancestor),
+                    None => false,
+                },
+                Combinator::Descendant => ancestors
+                    .iter()
+                    .enumerate()
+                    .any(|(i, anc)| matches(anc, &ancestors[..i], ancestor)),
+            }
+        }
+    }
+}
+
+/// select a simple selector matches a given element
+fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
+    // check type selector
+    if selector
+        .tag_name
+        .iter()
+        .any(|tag_name| elem.tag_name() != tag_name)
+    {
+        return false;
+    }
+
+    // check ID selector
+    if selector.id.iter().any(|id| elem.id() != Some(id)) {
+        return false;
+    }
+
+    // check class selectors
+    let elem_classes = elem.classes();
+    if selector
+        .class
+        .iter()
+        .any(|class| !elem_classes.contains(class.as_str()))
+    {
+        return false;
+    }
+
+    // no selector part failed to match
+    return true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Declaration;
+    use crate::dom::AttrMap;
+
+    fn elem(tag: &str, children: Vec<Node>) -> Node {
+        Node::element(tag.to_string(), AttrMap::new(), children)
+    }
+
+    fn simple(tag_name: &str) -> SimpleSelector {
+        SimpleSelector {
+            tag_name: Some(tag_name.to_string()),
+            id: None,
+            class: Vec::new(),
+        }
+    }
+
+    fn combinator_rule(ancestor_tag: &str, combinator: Combinator, subject_tag: &str) -> Rule {
+        Rule {
+            selectors: vec![Selector::Combinator {
+                ancestor: Box::new(Selector::Simple(simple(ancestor_tag))),
+                combinator,
+                subject: simple(subject_tag),
+            }],
+            declarations: vec![Declaration {
+                name: "color".to_string(),
+                value: Value::Keyword("red".to_string()),
+            }],
+        }
+    }
+
+    #[test]
+    fn child_combinator_matches_only_the_immediate_parent() {
+        // <div><p><span></span></p></div>
+        let span = elem("span", Vec::new());
+        let p = elem("p", vec![span]);
+        let tree = elem("div", vec![p]);
+
+        // `div > span` must not match: div is a grandparent, not the parent
+        let not_parent = StyleSheet {
+            rules: vec![combinator_rule("div", Combinator::Child, "span")],
+        };
+        let styled = style_tree(&tree, &not_parent);
+        let span_node = &styled.children[0].children[0];
+        assert!(!span_node.specified_values.contains_key("color"));
+
+        // `p > span` must match: p is the immediate parent
+        let is_parent = StyleSheet {
+            rules: vec![combinator_rule("p", Combinator::Child, "span")],
+        };
+        let styled = style_tree(&tree, &is_parent);
+        let span_node = &styled.children[0].children[0];
+        assert!(span_node.specified_values.contains_key("color"));
+    }
+
+    #[test]
+    fn descendant_combinator_matches_any_ancestor() {
+        // <div><p><span></span></p></div>
+        let span = elem("span", Vec::new());
+        let p = elem("p", vec![span]);
+        let tree = elem("div", vec![p]);
+
+        let stylesheet = StyleSheet {
+            rules: vec![combinator_rule("div", Combinator::Descendant, "span")],
+        };
+        let styled = style_tree(&tree, &stylesheet);
+        let span_node = &styled.children[0].children[0];
+        assert!(span_node.specified_values.contains_key("color"));
+    }
+}